@@ -6,6 +6,7 @@ use std::{
 
 use bitwarden_json::client::Client;
 use tokio::task::JoinHandle;
+use zeroize::Zeroizing;
 
 use crate::{box_ptr, ffi_ref};
 
@@ -23,11 +24,13 @@ pub extern "C" fn run_command(c_str_ptr: *const c_char, client_ptr: *const CClie
     let input_str = str::from_utf8(unsafe { CStr::from_ptr(c_str_ptr) }.to_bytes())
         .expect("Input should be a valid string");
 
-    let result = client
+    let mut result = client
         .runtime
-        .block_on(client.client.run_command(input_str));
+        .block_on(client.client.run_command_zeroizing(input_str));
 
-    match std::ffi::CString::new(result) {
+    // The response has room for the NUL terminator, so this doesn't reallocate and leave a copy
+    // behind. The caller owns the returned string.
+    match std::ffi::CString::new(std::mem::take(&mut *result)) {
         Ok(cstr) => cstr.into_raw(),
         Err(_) => panic!("failed to return command result: null encountered"),
     }
@@ -51,8 +54,11 @@ pub extern "C" fn run_command_async(
         .to_owned();
 
     let join_handle = client.runtime.spawn(async move {
-        let result = client.client.run_command(input_str.as_str()).await;
-        let str_result = match std::ffi::CString::new(result) {
+        let mut result = client
+            .client
+            .run_command_zeroizing(input_str.as_str())
+            .await;
+        let str_result = match std::ffi::CString::new(std::mem::take(&mut *result)) {
             Ok(cstr) => cstr.into_raw(),
             Err(_) => panic!("failed to return comment result: null encountered"),
         };
@@ -60,7 +66,8 @@ pub extern "C" fn run_command_async(
         // run completed function
         unsafe {
             on_completed_callback(str_result);
-            let _ = CString::from_raw(str_result);
+            // Wipe the response once the callback has copied it
+            let _ = Zeroizing::new(CString::from_raw(str_result).into_bytes_with_nul());
         }
     });
 
@@ -4,12 +4,13 @@ use bitwarden::{
     generators::ClientGeneratorExt,
     secrets_manager::{ClientProjectsExt, ClientSecretsExt},
 };
+use zeroize::Zeroizing;
 
 #[cfg(feature = "secrets")]
 use crate::command::{GeneratorsCommand, ProjectsCommand, SecretsCommand};
 use crate::{
     command::Command,
-    response::{Response, ResponseIntoString},
+    response::{CapabilitiesResponse, Response, ResponseIntoString},
 };
 
 pub struct Client(bitwarden::Client);
@@ -21,6 +22,16 @@ impl Client {
     }
 
     pub async fn run_command(&self, input_str: &str) -> String {
+        // Move the response out of the wrapper, the caller takes ownership of the buffer
+        std::mem::take(&mut *self.run_command_zeroizing(input_str).await)
+    }
+
+    /// Same as [Client::run_command], but the serialized response is zeroized when dropped.
+    /// Bindings should prefer this when they copy the response into a host language string.
+    ///
+    /// This only covers the response string. The parsed command and the response data are still
+    /// dropped without being wiped.
+    pub async fn run_command_zeroizing(&self, input_str: &str) -> Zeroizing<String> {
         const SUBCOMMANDS_TO_CLEAN: &[&str] = &["Secrets"];
         let mut cmd_value: serde_json::Value = match serde_json::from_str(input_str) {
             Ok(cmd) => cmd,
@@ -52,6 +63,10 @@ impl Client {
         let client = &self.0;
 
         match cmd {
+            Command::GetCapabilities {} => {
+                Ok::<_, bitwarden::Error>(CapabilitiesResponse::new()).into_string()
+            }
+
             #[cfg(feature = "secrets")]
             Command::LoginAccessToken(req) => {
                 client.auth().login_access_token(&req).await.into_string()
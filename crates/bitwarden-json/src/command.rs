@@ -19,6 +19,12 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize, JsonSchema, Debug)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub enum Command {
+    /// Lists the commands supported by this build of the SDK, so bindings can check for a
+    /// command before using it. The version of the response format is part of every response.
+    ///
+    /// Returns: [CapabilitiesResponse](crate::response::CapabilitiesResponse)
+    GetCapabilities {},
+
     #[cfg(feature = "secrets")]
     /// Login with Secrets Manager Access Token
     ///
@@ -1,11 +1,18 @@
-use std::error::Error;
+use std::{error::Error, io};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// The version of the response format. It's increased whenever the shape of an existing response
+/// changes, so bindings can detect responses they don't know how to read.
+pub const PROTOCOL_VERSION: u32 = 1;
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 #[serde(rename_all = "camelCase", deny_unknown_fields)]
 pub struct Response<T: Serialize + JsonSchema> {
+    /// The version of the response format, see [PROTOCOL_VERSION].
+    pub version: u32,
     /// Whether or not the SDK request succeeded.
     pub success: bool,
     /// A message for any error that may occur. Populated if `success` is false.
@@ -18,11 +25,13 @@ impl<T: Serialize + JsonSchema> Response<T> {
     pub fn new<TErr: Error>(response: Result<T, TErr>) -> Self {
         match response {
             Ok(data) => Self {
+                version: PROTOCOL_VERSION,
                 success: true,
                 error_message: None,
                 data: Some(data),
             },
             Err(err) => Self {
+                version: PROTOCOL_VERSION,
                 success: false,
                 error_message: Some(err.to_string()),
                 data: None,
@@ -34,6 +43,7 @@ impl<T: Serialize + JsonSchema> Response<T> {
 impl Response<()> {
     pub fn error(message: String) -> Self {
         Self {
+            version: PROTOCOL_VERSION,
             success: false,
             error_message: Some(message),
             data: None,
@@ -41,24 +51,174 @@ impl Response<()> {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct CapabilitiesResponse {
+    /// The commands supported by this build of the SDK, written as the path of the command in
+    /// the request, e.g. `secrets.get`.
+    pub commands: Vec<String>,
+}
+
+impl CapabilitiesResponse {
+    pub fn new() -> Self {
+        Self {
+            commands: SUPPORTED_COMMANDS.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+}
+
+impl Default for CapabilitiesResponse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const SUPPORTED_COMMANDS: &[&str] = &[
+    "getCapabilities",
+    #[cfg(feature = "secrets")]
+    "loginAccessToken",
+    #[cfg(feature = "secrets")]
+    "secrets.get",
+    #[cfg(feature = "secrets")]
+    "secrets.getByIds",
+    #[cfg(feature = "secrets")]
+    "secrets.create",
+    #[cfg(feature = "secrets")]
+    "secrets.list",
+    #[cfg(feature = "secrets")]
+    "secrets.update",
+    #[cfg(feature = "secrets")]
+    "secrets.delete",
+    #[cfg(feature = "secrets")]
+    "secrets.sync",
+    #[cfg(feature = "secrets")]
+    "projects.get",
+    #[cfg(feature = "secrets")]
+    "projects.create",
+    #[cfg(feature = "secrets")]
+    "projects.list",
+    #[cfg(feature = "secrets")]
+    "projects.update",
+    #[cfg(feature = "secrets")]
+    "projects.delete",
+    #[cfg(feature = "secrets")]
+    "generators.generatePassword",
+];
+
 pub(crate) trait ResponseIntoString {
-    fn into_string(self) -> String;
+    fn into_string(self) -> Zeroizing<String>;
 }
 
 impl<T: Serialize + JsonSchema, E: Error> ResponseIntoString for Result<T, E> {
-    fn into_string(self) -> String {
+    fn into_string(self) -> Zeroizing<String> {
         Response::new(self).into_string()
     }
 }
 
 impl<T: Serialize + JsonSchema> ResponseIntoString for Response<T> {
-    fn into_string(self) -> String {
-        match serde_json::to_string(&self) {
+    fn into_string(self) -> Zeroizing<String> {
+        match to_zeroizing_string(&self) {
             Ok(ser) => ser,
             Err(e) => {
                 let error = Response::error(format!("Failed to serialize Response: {}", e));
-                serde_json::to_string(&error).expect("Serialize should be infallible")
+                to_zeroizing_string(&error).expect("Serialize should be infallible")
+            }
+        }
+    }
+}
+
+/// Serializes the value into a JSON string which is zeroized on drop.
+///
+/// The serialized length is computed first so that the output buffer is allocated exactly once,
+/// otherwise every reallocation of a growing buffer would leave a partial copy of the response
+/// behind in freed memory. One extra byte is reserved so the C bindings can append the NUL
+/// terminator without reallocating either.
+fn to_zeroizing_string<T: Serialize>(value: &T) -> serde_json::Result<Zeroizing<String>> {
+    let mut counter = ByteCounter(0);
+    serde_json::to_writer(&mut counter, value)?;
+
+    let mut buffer = Zeroizing::new(Vec::with_capacity(counter.0 + 1));
+    serde_json::to_writer(&mut *buffer, value)?;
+
+    // This moves the allocation into the String without copying it
+    let string = String::from_utf8(std::mem::take(&mut *buffer))
+        .expect("serde_json should always produce valid UTF-8");
+    Ok(Zeroizing::new(string))
+}
+
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+    use crate::command::Command;
+
+    #[test]
+    fn to_zeroizing_string_matches_serde_json() {
+        let response = Response::new(Ok::<_, std::fmt::Error>(vec!["secret", "value"]));
+
+        let string = to_zeroizing_string(&response).unwrap();
+
+        assert_eq!(*string, serde_json::to_string(&response).unwrap());
+        assert_eq!(string.capacity(), string.len() + 1);
+    }
+
+    /// Collects the paths of all commands from the schema of [Command], which uses the same names
+    /// as the serialized commands.
+    fn command_paths(
+        schema: &serde_json::Value,
+        definitions: &serde_json::Value,
+        prefix: &str,
+        paths: &mut BTreeSet<String>,
+    ) {
+        for variant in schema["oneOf"].as_array().expect("commands are enums") {
+            let properties = variant["properties"]
+                .as_object()
+                .expect("variants are objects");
+            for (name, value) in properties {
+                let path = format!("{prefix}{name}");
+                let sub_command = value["$ref"]
+                    .as_str()
+                    .and_then(|r| r.strip_prefix("#/definitions/"))
+                    .map(|name| &definitions[name])
+                    .filter(|definition| definition.get("oneOf").is_some());
+
+                match sub_command {
+                    Some(definition) => {
+                        command_paths(definition, definitions, &format!("{path}."), paths)
+                    }
+                    None => {
+                        paths.insert(path);
+                    }
+                }
             }
         }
     }
+
+    #[test]
+    fn supported_commands_match_command_enum() {
+        let schema = serde_json::to_value(schemars::schema_for!(Command)).unwrap();
+
+        let mut paths = BTreeSet::new();
+        command_paths(&schema, &schema["definitions"], "", &mut paths);
+        // Debug commands only exist in debug builds and aren't advertised
+        paths.retain(|path| !path.starts_with("debug."));
+
+        let supported: BTreeSet<String> =
+            SUPPORTED_COMMANDS.iter().map(|c| c.to_string()).collect();
+        assert_eq!(paths, supported);
+    }
 }
@@ -1,5 +1,5 @@
 use bitwarden_json::client::Client as JsonClient;
-use pyo3::prelude::*;
+use pyo3::{prelude::*, types::PyString};
 
 #[pyclass]
 pub struct BitwardenClient(tokio::runtime::Runtime, JsonClient);
@@ -22,7 +22,10 @@ impl BitwardenClient {
     }
 
     #[pyo3(text_signature = "($self, command_input)")]
-    fn run_command(&self, command_input: String) -> String {
-        self.0.block_on(self.1.run_command(&command_input))
+    fn run_command<'py>(&self, py: Python<'py>, command_input: String) -> Bound<'py, PyString> {
+        let result = self
+            .0
+            .block_on(self.1.run_command_zeroizing(&command_input));
+        PyString::new_bound(py, &result)
     }
 }
@@ -61,12 +61,17 @@ mod readme {}
 pub use bitwarden_core::*;
 pub mod error;
 
-#[cfg(feature = "secrets")]
+#[cfg(feature = "generators")]
 pub mod generators {
-    pub use bitwarden_generators::{ClientGeneratorExt, PasswordError, PasswordGeneratorRequest};
+    pub use bitwarden_generators::{
+        ClientGeneratorExt, PassphraseError, PassphraseGeneratorRequest, PasswordError,
+        PasswordGeneratorRequest,
+    };
 }
 
 #[cfg(feature = "secrets")]
 pub mod secrets_manager {
     pub use bitwarden_sm::*;
+
+    pub mod terraform;
 }
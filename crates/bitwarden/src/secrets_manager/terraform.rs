@@ -0,0 +1,29 @@
+//! Helpers for exposing secrets to Terraform
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use super::secrets::SecretResponse;
+
+#[derive(Debug, Error)]
+#[error("Multiple secrets with name: '{0}'")]
+pub struct DuplicateSecretKeyError(pub String);
+
+/// Builds a map of secret key to secret value, the shape the Terraform `external` data source
+/// expects. Keys are sorted so the output is stable, and duplicate keys are rejected instead of
+/// depending on the order the secrets were returned in.
+pub fn secrets_map(
+    secrets: &[SecretResponse],
+) -> Result<BTreeMap<String, String>, DuplicateSecretKeyError> {
+    let mut map = BTreeMap::new();
+    for secret in secrets {
+        if map
+            .insert(secret.key.clone(), secret.value.clone())
+            .is_some()
+        {
+            return Err(DuplicateSecretKeyError(secret.key.clone()));
+        }
+    }
+    Ok(map)
+}
@@ -6,6 +6,7 @@ use clap_complete::Shell;
 use uuid::Uuid;
 
 pub(crate) const ACCESS_TOKEN_KEY_VAR_NAME: &str = "BWS_ACCESS_TOKEN";
+pub(crate) const ACCESS_TOKEN_FILE_KEY_VAR_NAME: &str = "BWS_ACCESS_TOKEN_FILE";
 pub(crate) const CONFIG_FILE_KEY_VAR_NAME: &str = "BWS_CONFIG_FILE";
 pub(crate) const PROFILE_KEY_VAR_NAME: &str = "BWS_PROFILE";
 pub(crate) const SERVER_URL_KEY_VAR_NAME: &str = "BWS_SERVER_URL";
@@ -32,6 +33,7 @@ pub(crate) enum Output {
     Env,
     Table,
     TSV,
+    Terraform,
     None,
 }
 
@@ -48,9 +50,31 @@ pub(crate) struct Cli {
     #[arg(short = 'c', long, global = true, value_enum, default_value_t = Color::Auto, help="Use colors in the output")]
     pub(crate) color: Color,
 
-    #[arg(short = 't', long, global = true, env = ACCESS_TOKEN_KEY_VAR_NAME, hide_env_values = true, help="Specify access token for the service account")]
+    #[arg(
+        long,
+        global = true,
+        help = "Sort the keys of JSON and YAML output, so the output is stable and can be diffed"
+    )]
+    pub(crate) canonical: bool,
+
+    #[arg(short = 't', long, global = true, env = ACCESS_TOKEN_KEY_VAR_NAME, hide_env_values = true, help="Specify access token for the service account. Only one of --access-token, --access-token-file and --access-token-command can be given, and it takes precedence over the environment variables")]
     pub(crate) access_token: Option<String>,
 
+    #[arg(
+        long,
+        global = true,
+        env = ACCESS_TOKEN_FILE_KEY_VAR_NAME,
+        help = "Read the access token from a file. The BWS_ACCESS_TOKEN variable takes precedence over BWS_ACCESS_TOKEN_FILE"
+    )]
+    pub(crate) access_token_file: Option<PathBuf>,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Run a command and use its output as the access token. Takes precedence over the BWS_ACCESS_TOKEN and BWS_ACCESS_TOKEN_FILE variables"
+    )]
+    pub(crate) access_token_command: Option<String>,
+
     #[arg(
         short = 'f',
         long,
@@ -111,6 +135,25 @@ pub(crate) enum Commands {
             help = "Use the secret UUID (in its POSIX form) instead of the key name for the environment variable"
         )]
         uuids_as_keynames: bool,
+        #[arg(
+            long,
+            help = "Expand references to other secrets, written as {{bws:KEY}} or {{bws:SECRET_ID}}, inside secret values"
+        )]
+        resolve_references: bool,
+        #[arg(
+            long,
+            help = "A manifest file mapping environment variables to secrets [default: bws.yml when --env is used]"
+        )]
+        manifest: Option<PathBuf>,
+        #[arg(long = "env", help = "The environment in the manifest file to use")]
+        environment: Option<String>,
+    },
+    #[command(
+        long_about = "Generate passwords and passphrases, printing one per line unless --output is given. The env output format isn't supported"
+    )]
+    Generate {
+        #[command(subcommand)]
+        cmd: GenerateCommand,
     },
 }
 
@@ -118,12 +161,18 @@ pub(crate) enum Commands {
 pub(crate) enum SecretCommand {
     Create {
         key: String,
+        #[arg(
+            help = "The value of the secret. Use `@path` to read it from a file or `-` to read it from stdin, keeping any trailing newline. Start the value with `@@` to store a literal `@`"
+        )]
         value: String,
 
         #[arg(help = "The ID of the project this secret will be added to")]
         project_id: Uuid,
 
-        #[arg(long, help = "An optional note to add to the secret")]
+        #[arg(
+            long,
+            help = "An optional note to add to the secret. Supports `@path` and `-` like the value"
+        )]
         note: Option<String>,
     },
     Delete {
@@ -134,9 +183,17 @@ pub(crate) enum SecretCommand {
         secret_id: Uuid,
         #[arg(long, group = "edit_field")]
         key: Option<String>,
-        #[arg(long, group = "edit_field")]
+        #[arg(
+            long,
+            group = "edit_field",
+            help = "Use `@path` to read the value from a file or `-` to read it from stdin, keeping any trailing newline. Start the value with `@@` to store a literal `@`"
+        )]
         value: Option<String>,
-        #[arg(long, group = "edit_field")]
+        #[arg(
+            long,
+            group = "edit_field",
+            help = "Use `@path` to read the note from a file or `-` to read it from stdin, keeping any trailing newline. Start the note with `@@` to store a literal `@`"
+        )]
         note: Option<String>,
         #[arg(long, group = "edit_field")]
         project_id: Option<Uuid>,
@@ -166,4 +223,64 @@ pub(crate) enum ProjectCommand {
         project_id: Uuid,
     },
     List,
+    #[command(
+        long_about = "Compare the secrets of two projects by key. The env output format isn't supported"
+    )]
+    Diff {
+        #[arg(help = "The ID of the project to compare from")]
+        source_project_id: Uuid,
+        #[arg(help = "The ID of the project to compare to")]
+        target_project_id: Uuid,
+        #[arg(
+            long,
+            help = "Show secret values instead of hashes. Hashes use a random key on every run, so they can only be compared within the same output"
+        )]
+        show_values: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum GenerateCommand {
+    Password {
+        #[arg(long, default_value_t = 16, help = "The length of the password")]
+        length: u8,
+        #[arg(long, help = "Don't include lowercase characters")]
+        no_lowercase: bool,
+        #[arg(long, help = "Don't include uppercase characters")]
+        no_uppercase: bool,
+        #[arg(long, help = "Don't include numbers")]
+        no_numbers: bool,
+        #[arg(long, help = "Include special characters")]
+        symbols: bool,
+        #[arg(long, help = "Avoid characters that look alike, like 0 and O")]
+        avoid_ambiguous: bool,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "The number of passwords to generate"
+        )]
+        count: u32,
+    },
+    Passphrase {
+        #[arg(
+            long,
+            default_value_t = 3,
+            help = "The number of words in the passphrase"
+        )]
+        words: u8,
+        #[arg(long, default_value = "-", help = "The separator between words")]
+        separator: String,
+        #[arg(long, help = "Capitalize the first letter of each word")]
+        capitalize: bool,
+        #[arg(long, help = "Add a number to one of the words")]
+        include_number: bool,
+        #[arg(
+            long,
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u32).range(1..),
+            help = "The number of passphrases to generate"
+        )]
+        count: u32,
+    },
 }
@@ -0,0 +1,105 @@
+use bitwarden::{
+    generators::{ClientGeneratorExt, PassphraseGeneratorRequest, PasswordGeneratorRequest},
+    Client,
+};
+use color_eyre::eyre::Result;
+
+use crate::{
+    render::{serialize_response, OutputSettings},
+    GenerateCommand,
+};
+
+/// Prints the generated values, one per line, or in the requested output format.
+pub(crate) fn process_command(
+    command: GenerateCommand,
+    output_settings: Option<OutputSettings>,
+) -> Result<()> {
+    // Generating doesn't talk to the server, so there's no need to log in
+    let client = Client::new(None);
+
+    let values = match command {
+        GenerateCommand::Password {
+            length,
+            no_lowercase,
+            no_uppercase,
+            no_numbers,
+            symbols,
+            avoid_ambiguous,
+            count,
+        } => (0..count)
+            .map(|_| {
+                client.generator().password(PasswordGeneratorRequest {
+                    lowercase: !no_lowercase,
+                    uppercase: !no_uppercase,
+                    numbers: !no_numbers,
+                    special: symbols,
+                    length,
+                    avoid_ambiguous,
+                    min_lowercase: None,
+                    min_uppercase: None,
+                    min_number: None,
+                    min_special: None,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        GenerateCommand::Passphrase {
+            words,
+            separator,
+            capitalize,
+            include_number,
+            count,
+        } => (0..count)
+            .map(|_| {
+                client.generator().passphrase(PassphraseGeneratorRequest {
+                    num_words: words,
+                    word_separator: separator.clone(),
+                    capitalize,
+                    include_number,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    match output_settings {
+        Some(output_settings) => serialize_response(values, output_settings),
+        None => {
+            for value in values {
+                println!("{value}");
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use crate::{Cli, Commands, GenerateCommand};
+
+    #[test]
+    fn parse_generate_password() {
+        let args = "bws generate password --length 24 --symbols --count 3";
+        let cli = Cli::try_parse_from(args.split(' ')).unwrap();
+
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Generate {
+                cmd: GenerateCommand::Password {
+                    length: 24,
+                    symbols: true,
+                    no_lowercase: false,
+                    count: 3,
+                    ..
+                }
+            })
+        ));
+    }
+
+    #[test]
+    fn parse_generate_rejects_zero_count() {
+        let args = "bws generate passphrase --count 0";
+        assert!(Cli::try_parse_from(args.split(' ')).is_err());
+    }
+}
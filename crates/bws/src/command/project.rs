@@ -1,18 +1,24 @@
+use std::collections::BTreeMap;
+
 use bitwarden::{
     secrets_manager::{
         projects::{
             ProjectCreateRequest, ProjectGetRequest, ProjectPutRequest, ProjectsDeleteRequest,
             ProjectsListRequest,
         },
-        ClientProjectsExt,
+        secrets::{SecretIdentifiersByProjectRequest, SecretsGetRequest},
+        ClientProjectsExt, ClientSecretsExt,
     },
     Client,
 };
 use color_eyre::eyre::{bail, Result};
+use hmac::{Hmac, Mac};
+use itertools::Itertools;
+use sha2::Sha256;
 use uuid::Uuid;
 
 use crate::{
-    render::{serialize_response, OutputSettings},
+    render::{serialize_response, OutputSettings, SecretDiff, SecretDiffStatus},
     ProjectCommand,
 };
 
@@ -32,6 +38,20 @@ pub(crate) async fn process_command(
             edit(client, organization_id, project_id, name, output_settings).await
         }
         ProjectCommand::Delete { project_ids } => delete(client, project_ids).await,
+        ProjectCommand::Diff {
+            source_project_id,
+            target_project_id,
+            show_values,
+        } => {
+            diff(
+                client,
+                source_project_id,
+                target_project_id,
+                show_values,
+                output_settings,
+            )
+            .await
+        }
     }
 }
 
@@ -139,3 +159,163 @@ pub(crate) async fn delete(client: Client, project_ids: Vec<Uuid>) -> Result<()>
 
     Ok(())
 }
+
+pub(crate) async fn diff(
+    client: Client,
+    source_project_id: Uuid,
+    target_project_id: Uuid,
+    show_values: bool,
+    output_settings: OutputSettings,
+) -> Result<()> {
+    let source = get_project_secrets(&client, source_project_id).await?;
+    let target = get_project_secrets(&client, target_project_id).await?;
+
+    let hasher = ValueHasher::new();
+    let display_value = |value: &String| {
+        if show_values {
+            value.clone()
+        } else {
+            hasher.hash(value)
+        }
+    };
+
+    let diff: Vec<SecretDiff> = compare_secrets(&source, &target)
+        .into_iter()
+        .map(|(status, key)| SecretDiff {
+            status,
+            source_value: source.get(&key).map(display_value),
+            target_value: target.get(&key).map(display_value),
+            key,
+        })
+        .collect();
+    serialize_response(diff, output_settings);
+
+    Ok(())
+}
+
+async fn get_project_secrets(
+    client: &Client,
+    project_id: Uuid,
+) -> Result<BTreeMap<String, String>> {
+    let secret_ids = client
+        .secrets()
+        .list_by_project(&SecretIdentifiersByProjectRequest { project_id })
+        .await?
+        .data
+        .into_iter()
+        .map(|e| e.id)
+        .collect();
+    let secrets = client
+        .secrets()
+        .get_by_ids(SecretsGetRequest { ids: secret_ids })
+        .await?
+        .data;
+
+    if let Some(duplicate) = secrets.iter().map(|s| &s.key).duplicates().next() {
+        bail!(
+            "Multiple secrets with name: '{}' in project {}. Use unique names for secrets to compare projects",
+            duplicate,
+            project_id
+        );
+    }
+
+    Ok(secrets.into_iter().map(|s| (s.key, s.value)).collect())
+}
+
+/// Compares two sets of secrets by key, returning the keys that differ sorted by name. Keys only
+/// present in `target` are reported as added, and keys only present in `source` as removed.
+fn compare_secrets(
+    source: &BTreeMap<String, String>,
+    target: &BTreeMap<String, String>,
+) -> Vec<(SecretDiffStatus, String)> {
+    source
+        .keys()
+        .chain(target.keys())
+        .sorted()
+        .dedup()
+        .filter_map(|key| {
+            let status = match (source.get(key), target.get(key)) {
+                (None, Some(_)) => SecretDiffStatus::Added,
+                (Some(_), None) => SecretDiffStatus::Removed,
+                (Some(a), Some(b)) if a != b => SecretDiffStatus::Changed,
+                _ => return None,
+            };
+            Some((status, key.clone()))
+        })
+        .collect()
+}
+
+/// Fingerprints secret values, which allows spotting differences without printing the values
+/// themselves. A random key is used for every run, so the fingerprints can't be brute-forced
+/// offline to recover low-entropy values, and only compare values within the same output.
+struct ValueHasher {
+    key: [u8; 32],
+}
+
+impl ValueHasher {
+    fn new() -> Self {
+        Self {
+            key: rand::random(),
+        }
+    }
+
+    fn hash(&self, value: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC can take a key of any size");
+        mac.update(value.as_bytes());
+        let hash = format!("{:x}", mac.finalize().into_bytes());
+        format!("hmac-sha256:{}", &hash[..16])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secrets(entries: &[(&str, &str)]) -> BTreeMap<String, String> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn compare_secrets_reports_added_removed_and_changed() {
+        let source = secrets(&[("A", "1"), ("B", "2"), ("C", "3")]);
+        let target = secrets(&[("B", "2"), ("C", "4"), ("D", "5")]);
+
+        assert_eq!(
+            compare_secrets(&source, &target),
+            vec![
+                (SecretDiffStatus::Removed, "A".to_string()),
+                (SecretDiffStatus::Changed, "C".to_string()),
+                (SecretDiffStatus::Added, "D".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn compare_secrets_identical_is_empty() {
+        let source = secrets(&[("A", "1")]);
+
+        assert!(compare_secrets(&source, &source.clone()).is_empty());
+    }
+
+    #[test]
+    fn value_hasher_is_stable_within_a_run() {
+        let hasher = ValueHasher::new();
+        let hash = hasher.hash("hunter2");
+
+        assert_eq!(hash, hasher.hash("hunter2"));
+        assert_ne!(hash, hasher.hash("hunter3"));
+        assert!(!hash.contains("hunter2"));
+    }
+
+    #[test]
+    fn value_hasher_uses_a_random_key() {
+        assert_ne!(
+            ValueHasher::new().hash("hunter2"),
+            ValueHasher::new().hash("hunter2")
+        );
+    }
+}
@@ -1,12 +1,16 @@
 use std::{
     collections::HashMap,
     io::{IsTerminal, Read},
+    path::PathBuf,
     process,
 };
 
 use bitwarden::{
     secrets_manager::{
-        secrets::{SecretIdentifiersByProjectRequest, SecretIdentifiersRequest, SecretsGetRequest},
+        secrets::{
+            SecretIdentifiersByProjectRequest, SecretIdentifiersRequest, SecretResponse,
+            SecretsGetRequest,
+        },
         ClientSecretsExt,
     },
     Client,
@@ -17,22 +21,48 @@ use uuid::Uuid;
 use which::which;
 
 use crate::{
+    manifest::{Manifest, ManifestVariable, DEFAULT_MANIFEST_FILENAME},
+    reference::SecretReferences,
     util::{is_valid_posix_name, uuid_to_posix},
-    ACCESS_TOKEN_KEY_VAR_NAME,
+    ACCESS_TOKEN_FILE_KEY_VAR_NAME, ACCESS_TOKEN_KEY_VAR_NAME,
 };
 
 // Essential environment variables that should be preserved even when `--no-inherit-env` is used
 const WINDOWS_ESSENTIAL_VARS: &[&str] = &["SystemRoot", "ComSpec", "windir"];
 
+#[derive(Debug)]
+pub(crate) struct RunCommandModel {
+    pub(crate) command: Vec<String>,
+    pub(crate) shell: Option<String>,
+    pub(crate) no_inherit_env: bool,
+    pub(crate) project_id: Option<Uuid>,
+    pub(crate) uuids_as_keynames: bool,
+    pub(crate) resolve_references: bool,
+    pub(crate) manifest: Option<PathBuf>,
+    pub(crate) environment: Option<String>,
+}
+
 pub(crate) async fn run(
     client: Client,
     organization_id: Uuid,
-    project_id: Option<Uuid>,
-    uuids_as_keynames: bool,
-    no_inherit_env: bool,
-    shell: Option<String>,
-    command: Vec<String>,
+    run_command: RunCommandModel,
 ) -> Result<i32> {
+    let RunCommandModel {
+        command,
+        shell,
+        no_inherit_env,
+        project_id,
+        uuids_as_keynames,
+        resolve_references,
+        manifest,
+        environment,
+    } = run_command;
+
+    // Not a clap conflict, as that would also be triggered by BWS_UUIDS_AS_KEYNAMES=false
+    if uuids_as_keynames && (manifest.is_some() || environment.is_some()) {
+        bail!("--uuids-as-keynames can't be used together with --manifest or --env");
+    }
+
     let is_windows = std::env::consts::OS == "windows";
 
     let shell = shell.unwrap_or_else(|| {
@@ -59,49 +89,52 @@ pub(crate) async fn run(
         command.join(" ")
     };
 
-    let res = if let Some(project_id) = project_id {
-        client
-            .secrets()
-            .list_by_project(&SecretIdentifiersByProjectRequest { project_id })
-            .await?
+    let manifest = match (manifest, &environment) {
+        (Some(path), _) => Some(path),
+        (None, Some(_)) => Some(PathBuf::from(DEFAULT_MANIFEST_FILENAME)),
+        (None, None) => None,
+    };
+
+    let environment = if let Some(manifest) = manifest {
+        let variables = Manifest::load(&manifest)?.variables(environment.as_deref(), project_id)?;
+        manifest_environment(&client, organization_id, variables, resolve_references).await?
     } else {
-        client
+        let secret_ids = list_secret_ids(&client, organization_id, project_id).await?;
+        let mut secrets = client
             .secrets()
-            .list(&SecretIdentifiersRequest { organization_id })
+            .get_by_ids(SecretsGetRequest { ids: secret_ids })
             .await?
-    };
-
-    let secret_ids = res.data.into_iter().map(|e| e.id).collect();
-    let secrets = client
-        .secrets()
-        .get_by_ids(SecretsGetRequest { ids: secret_ids })
-        .await?
-        .data;
+            .data;
 
-    if !uuids_as_keynames {
-        if let Some(duplicate) = secrets.iter().map(|s| &s.key).duplicates().next() {
-            bail!("Multiple secrets with name: '{}'. Use --uuids-as-keynames or use unique names for secrets", duplicate);
+        if resolve_references {
+            secrets = resolve_secret_references(&client, organization_id, secrets).await?;
         }
-    }
 
-    let environment: HashMap<String, String> = secrets
-        .into_iter()
-        .map(|s| {
-            if uuids_as_keynames {
-                (uuid_to_posix(&s.id), s.value)
-            } else {
-                (s.key, s.value)
-            }
-        })
-        .inspect(|(k, _)| {
-            if !is_valid_posix_name(k) {
-                eprintln!(
-                    "Warning: secret '{}' does not have a POSIX-compliant name",
-                    k
-                );
+        if !uuids_as_keynames {
+            if let Some(duplicate) = secrets.iter().map(|s| &s.key).duplicates().next() {
+                bail!("Multiple secrets with name: '{}'. Use --uuids-as-keynames or use unique names for secrets", duplicate);
             }
-        })
-        .collect();
+        }
+
+        secrets
+            .into_iter()
+            .map(|s| {
+                if uuids_as_keynames {
+                    (uuid_to_posix(&s.id), s.value)
+                } else {
+                    (s.key, s.value)
+                }
+            })
+            .inspect(|(k, _)| {
+                if !is_valid_posix_name(k) {
+                    eprintln!(
+                        "Warning: secret '{}' does not have a POSIX-compliant name",
+                        k
+                    );
+                }
+            })
+            .collect()
+    };
 
     let mut command = process::Command::new(shell);
     command
@@ -131,6 +164,7 @@ pub(crate) async fn run(
         command.envs(environment);
     } else {
         command.env_remove(ACCESS_TOKEN_KEY_VAR_NAME);
+        command.env_remove(ACCESS_TOKEN_FILE_KEY_VAR_NAME);
         command.envs(environment);
     }
 
@@ -147,3 +181,182 @@ pub(crate) async fn run(
         }
     }
 }
+
+/// Expands the references inside the secret values. Referenced secrets which weren't already
+/// fetched, like secrets from other projects, are looked up in the organization and fetched in
+/// batches.
+async fn resolve_secret_references(
+    client: &Client,
+    organization_id: Uuid,
+    mut secrets: Vec<SecretResponse>,
+) -> Result<Vec<SecretResponse>> {
+    let mut references = SecretReferences::new(&secrets);
+
+    let mut missing = references.missing();
+    if !missing.is_empty() {
+        let identifiers = client
+            .secrets()
+            .list(&SecretIdentifiersRequest { organization_id })
+            .await?
+            .data;
+
+        while !missing.is_empty() {
+            let ids: Vec<Uuid> = identifiers
+                .iter()
+                .filter(|i| missing.contains(&i.key) || missing.contains(&i.id.to_string()))
+                .map(|i| i.id)
+                .collect();
+            if ids.is_empty() {
+                break;
+            }
+
+            let fetched = client
+                .secrets()
+                .get_by_ids(SecretsGetRequest { ids })
+                .await?
+                .data;
+            if references.extend(&fetched) == 0 {
+                break;
+            }
+
+            missing = references.missing();
+        }
+    }
+
+    for secret in &mut secrets {
+        secret.value = references.resolve(&secret.value)?;
+    }
+
+    Ok(secrets)
+}
+
+async fn list_secret_ids(
+    client: &Client,
+    organization_id: Uuid,
+    project_id: Option<Uuid>,
+) -> Result<Vec<Uuid>> {
+    let res = if let Some(project_id) = project_id {
+        client
+            .secrets()
+            .list_by_project(&SecretIdentifiersByProjectRequest { project_id })
+            .await?
+    } else {
+        client
+            .secrets()
+            .list(&SecretIdentifiersRequest { organization_id })
+            .await?
+    };
+
+    Ok(res.data.into_iter().map(|e| e.id).collect())
+}
+
+/// Builds the environment from the variables of a manifest, fetching the secrets of all the
+/// projects they use in a single batch.
+async fn manifest_environment(
+    client: &Client,
+    organization_id: Uuid,
+    variables: Vec<ManifestVariable>,
+    resolve_references: bool,
+) -> Result<HashMap<String, String>> {
+    let mut secret_ids = Vec::new();
+    for project_id in variables.iter().map(|v| v.project_id).unique() {
+        secret_ids.extend(list_secret_ids(client, organization_id, project_id).await?);
+    }
+
+    let mut secrets = client
+        .secrets()
+        .get_by_ids(SecretsGetRequest {
+            ids: secret_ids.into_iter().unique().collect(),
+        })
+        .await?
+        .data;
+
+    if resolve_references {
+        secrets = resolve_secret_references(client, organization_id, secrets).await?;
+    }
+
+    variables
+        .into_iter()
+        .inspect(|v| {
+            if !is_valid_posix_name(&v.name) {
+                eprintln!(
+                    "Warning: variable '{}' does not have a POSIX-compliant name",
+                    v.name
+                );
+            }
+        })
+        .map(|v| {
+            let value = find_manifest_secret(&secrets, &v)?.value.clone();
+            Ok((v.name, value))
+        })
+        .collect()
+}
+
+/// Finds the secret for a manifest variable by key or ID, within its project if it has one
+fn find_manifest_secret<'a>(
+    secrets: &'a [SecretResponse],
+    variable: &ManifestVariable,
+) -> Result<&'a SecretResponse> {
+    let matches: Vec<&SecretResponse> = secrets
+        .iter()
+        .filter(|s| variable.project_id.is_none() || s.project_id == variable.project_id)
+        .filter(|s| s.key == variable.key || s.id.to_string() == variable.key)
+        .collect();
+
+    match matches.as_slice() {
+        [secret] => Ok(secret),
+        [] => bail!(
+            "Secret '{}' for variable '{}' could not be found",
+            variable.key,
+            variable.name
+        ),
+        _ => bail!(
+            "Secret '{}' for variable '{}' is ambiguous, {} secrets have that name. Use the secret ID instead",
+            variable.key,
+            variable.name,
+            matches.len()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing;
+
+    fn secret(key: &str, project_id: Option<Uuid>) -> SecretResponse {
+        SecretResponse {
+            project_id,
+            ..testing::secret(key, &format!("{key} value"))
+        }
+    }
+
+    fn variable(key: &str, project_id: Option<Uuid>) -> ManifestVariable {
+        ManifestVariable {
+            name: "VAR".to_string(),
+            key: key.to_string(),
+            project_id,
+        }
+    }
+
+    #[test]
+    fn find_manifest_secret_scopes_by_project() {
+        let (project_a, project_b) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let secrets = [secret("DB", Some(project_a)), secret("DB", Some(project_b))];
+
+        let found = find_manifest_secret(&secrets, &variable("DB", Some(project_b))).unwrap();
+        assert_eq!(found.id, secrets[1].id);
+
+        assert!(find_manifest_secret(&secrets, &variable("DB", None)).is_err());
+        assert!(find_manifest_secret(&secrets, &variable("API", Some(project_a))).is_err());
+    }
+
+    #[test]
+    fn find_manifest_secret_by_id() {
+        let secrets = [secret("DB", None), secret("DB", None)];
+
+        let found =
+            find_manifest_secret(&secrets, &variable(&secrets[0].id.to_string(), None)).unwrap();
+        assert_eq!(found.id, secrets[0].id);
+    }
+}
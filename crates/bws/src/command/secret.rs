@@ -12,7 +12,8 @@ use color_eyre::eyre::{bail, Result};
 use uuid::Uuid;
 
 use crate::{
-    render::{serialize_response, OutputSettings},
+    render::{serialize_secrets, OutputSettings},
+    util::ValueSource,
     SecretCommand,
 };
 
@@ -50,12 +51,13 @@ pub(crate) async fn process_command(
             note,
             project_id,
         } => {
+            let (value, note) = read_value_and_note(Some(value), note)?;
             create(
                 client,
                 organization_id,
                 SecretCreateCommandModel {
                     key,
-                    value,
+                    value: value.unwrap_or_default(),
                     note,
                     project_id,
                 },
@@ -70,6 +72,7 @@ pub(crate) async fn process_command(
             note,
             project_id,
         } => {
+            let (value, note) = read_value_and_note(value, note)?;
             edit(
                 client,
                 organization_id,
@@ -88,6 +91,24 @@ pub(crate) async fn process_command(
     }
 }
 
+/// Resolves the `value` and `note` arguments, either of which may point to a file or stdin
+fn read_value_and_note(
+    value: Option<String>,
+    note: Option<String>,
+) -> Result<(Option<String>, Option<String>)> {
+    let value_source = value.as_deref().map(ValueSource::parse);
+    let note_source = note.as_deref().map(ValueSource::parse);
+
+    if value_source == Some(ValueSource::Stdin) && note_source == Some(ValueSource::Stdin) {
+        bail!("Only one of the value and the note can be read from stdin");
+    }
+
+    Ok((
+        value_source.map(|s| s.read()).transpose()?,
+        note_source.map(|s| s.read()).transpose()?,
+    ))
+}
+
 pub(crate) async fn list(
     client: Client,
     organization_id: Uuid,
@@ -112,9 +133,7 @@ pub(crate) async fn list(
         .get_by_ids(SecretsGetRequest { ids: secret_ids })
         .await?
         .data;
-    serialize_response(secrets, output_settings);
-
-    Ok(())
+    serialize_secrets(secrets, output_settings)
 }
 
 pub(crate) async fn get(
@@ -126,9 +145,7 @@ pub(crate) async fn get(
         .secrets()
         .get(&SecretGetRequest { id: secret_id })
         .await?;
-    serialize_response(secret, output_settings);
-
-    Ok(())
+    serialize_secrets(secret, output_settings)
 }
 
 pub(crate) async fn create(
@@ -147,9 +164,7 @@ pub(crate) async fn create(
             project_ids: Some(vec![secret.project_id]),
         })
         .await?;
-    serialize_response(secret, output_settings);
-
-    Ok(())
+    serialize_secrets(secret, output_settings)
 }
 
 pub(crate) async fn edit(
@@ -177,9 +192,7 @@ pub(crate) async fn edit(
                 .map(|id| vec![id]),
         })
         .await?;
-    serialize_response(new_secret, output_settings);
-
-    Ok(())
+    serialize_secrets(new_secret, output_settings)
 }
 
 pub(crate) async fn delete(client: Client, secret_ids: Vec<Uuid>) -> Result<()> {
@@ -219,3 +232,15 @@ pub(crate) async fn delete(client: Client, secret_ids: Vec<Uuid>) -> Result<()>
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_value_and_note_rejects_both_from_stdin() {
+        let result = read_value_and_note(Some("-".to_string()), Some("-".to_string()));
+
+        assert!(result.is_err());
+    }
+}
@@ -5,7 +5,7 @@ use bitwarden::{
     ClientSettings,
 };
 use bitwarden_cli::install_color_eyre;
-use clap::{CommandFactory, Parser};
+use clap::{error::ErrorKind, parser::ValueSource, ArgMatches, CommandFactory, FromArgMatches};
 use color_eyre::eyre::{bail, Result};
 use config::Profile;
 use log::error;
@@ -14,22 +14,48 @@ use render::OutputSettings;
 mod cli;
 mod command;
 mod config;
+mod manifest;
+mod reference;
 mod render;
 mod state;
+#[cfg(test)]
+mod testing;
 mod util;
 
 use crate::cli::*;
 
+const USER_AGENT: &str = concat!("Bitwarden_SM_CLI/", env!("CARGO_PKG_VERSION"));
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
 
-    process_commands().await
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let output_requested = matches.value_source("output") == Some(ValueSource::CommandLine);
+    // JSON is the default output format, so only switch to machine readable errors when it was
+    // explicitly requested, to keep errors readable in interactive use
+    let json_errors = cli.output == Output::JSON && output_requested;
+
+    let access_token = access_token_source(&cli, &matches).unwrap_or_else(|e| e.exit());
+
+    let result = process_commands(cli, access_token, output_requested).await;
+
+    if let (Err(error), true) = (&result, json_errors) {
+        render::serialize_error(error);
+        std::process::exit(1);
+    }
+
+    result
 }
 
 #[allow(clippy::comparison_chain)]
-async fn process_commands() -> Result<()> {
-    let cli = Cli::parse();
+async fn process_commands(
+    cli: Cli,
+    access_token: AccessTokenSource,
+    output_requested: bool,
+) -> Result<()> {
     let color = cli.color;
 
     install_color_eyre(color)?;
@@ -40,33 +66,63 @@ async fn process_commands() -> Result<()> {
         std::process::exit(1);
     };
 
+    if cli.output == Output::Terraform && !matches!(command, Commands::Secret { .. }) {
+        bail!("The terraform output format is only supported by secret commands");
+    }
+    if cli.output == Output::Env
+        && matches!(
+            command,
+            Commands::Project {
+                cmd: ProjectCommand::Diff { .. }
+            }
+        )
+    {
+        bail!("The env output format is not supported by project diff");
+    }
+    if cli.output == Output::Env && matches!(command, Commands::Generate { .. }) {
+        bail!("The env output format is not supported by generate commands");
+    }
+
     // These commands don't require authentication, so we process them first
     match command {
         Commands::Completions { shell } => {
             return command::completions(shell);
         }
+        Commands::Generate { cmd } => {
+            // Generated values are printed one per line unless an output format was requested
+            let output_settings =
+                output_requested.then(|| OutputSettings::new(cli.output, color, cli.canonical));
+            return command::generate::process_command(cmd, output_settings);
+        }
         Commands::Config {
             name,
             value,
             delete,
         } => {
+            // The access token only selects the profile, so avoid running the helper command when
+            // the profile is given
+            let access_token = match cli.profile {
+                Some(_) => None,
+                None => access_token.read()?,
+            };
             return command::config(
                 name,
                 value,
                 delete,
                 cli.profile,
-                cli.access_token,
+                access_token,
                 cli.config_file,
             );
         }
         _ => (),
     }
 
-    let access_token = match cli.access_token {
+    let access_token = match access_token.read()? {
         Some(key) => key,
         None => bail!("Missing access token"),
     };
-    let access_token_obj: AccessToken = access_token.parse()?;
+    // Convert to the SDK error, so it's reported as an invalid access token in JSON errors
+    let access_token_obj: AccessToken = access_token.parse().map_err(bitwarden::Error::from)?;
 
     let profile = get_config_profile(
         &cli.server_url,
@@ -84,7 +140,14 @@ async fn process_commands() -> Result<()> {
                 ..Default::default()
             })
         })
-        .transpose()?;
+        .transpose()?
+        .unwrap_or_default();
+
+    // Identify bws to the server instead of sending the generic SDK user agent
+    let settings = ClientSettings {
+        user_agent: USER_AGENT.to_string(),
+        ..settings
+    };
 
     let state_file = match get_state_opt_out(&profile) {
         true => None,
@@ -100,7 +163,7 @@ async fn process_commands() -> Result<()> {
         },
     };
 
-    let client = bitwarden::Client::new(settings);
+    let client = bitwarden::Client::new(Some(settings));
 
     // Load session or return if no session exists
     let _ = client
@@ -119,7 +182,7 @@ async fn process_commands() -> Result<()> {
         }
     };
 
-    let output_settings = OutputSettings::new(cli.output, color);
+    let output_settings = OutputSettings::new(cli.output, color, cli.canonical);
 
     // And finally we process all the commands which require authentication
     match command {
@@ -137,15 +200,23 @@ async fn process_commands() -> Result<()> {
             no_inherit_env,
             project_id,
             uuids_as_keynames,
+            resolve_references,
+            manifest,
+            environment,
         } => {
             let exit_code = command::run::run(
                 client,
                 organization_id,
-                project_id,
-                uuids_as_keynames,
-                no_inherit_env,
-                shell,
-                command,
+                command::run::RunCommandModel {
+                    command,
+                    shell,
+                    no_inherit_env,
+                    project_id,
+                    uuids_as_keynames,
+                    resolve_references,
+                    manifest,
+                    environment,
+                },
             )
             .await?;
 
@@ -153,12 +224,68 @@ async fn process_commands() -> Result<()> {
             std::process::exit(exit_code);
         }
 
-        Commands::Config { .. } | Commands::Completions { .. } => {
+        Commands::Config { .. } | Commands::Completions { .. } | Commands::Generate { .. } => {
             unreachable!()
         }
     }
 }
 
+/// Where the access token is read from. Reading it can run a command, so it's only done once
+/// the token is needed.
+#[derive(Debug, PartialEq)]
+enum AccessTokenSource {
+    Token(String),
+    File(PathBuf),
+    Command(String),
+    None,
+}
+
+impl AccessTokenSource {
+    fn read(self) -> Result<Option<String>> {
+        match self {
+            AccessTokenSource::Token(token) => Ok(Some(token)),
+            AccessTokenSource::File(path) => util::read_access_token_file(&path).map(Some),
+            AccessTokenSource::Command(command) => {
+                util::run_access_token_command(&command).map(Some)
+            }
+            AccessTokenSource::None => Ok(None),
+        }
+    }
+}
+
+/// Picks the source of the access token. At most one of `--access-token`, `--access-token-file`
+/// and `--access-token-command` can be given on the command line, and it takes precedence over
+/// the `BWS_ACCESS_TOKEN` and `BWS_ACCESS_TOKEN_FILE` environment variables, in that order.
+fn access_token_source(cli: &Cli, matches: &ArgMatches) -> Result<AccessTokenSource, clap::Error> {
+    let from_command_line = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    let ids = ["access_token", "access_token_file", "access_token_command"];
+    if ids.iter().filter(|id| from_command_line(id)).count() > 1 {
+        return Err(Cli::command().error(
+            ErrorKind::ArgumentConflict,
+            "only one of --access-token, --access-token-file and --access-token-command can be used",
+        ));
+    }
+
+    let source = match (
+        &cli.access_token,
+        &cli.access_token_file,
+        &cli.access_token_command,
+    ) {
+        (Some(token), _, _) if from_command_line("access_token") => {
+            AccessTokenSource::Token(token.clone())
+        }
+        (_, Some(path), _) if from_command_line("access_token_file") => {
+            AccessTokenSource::File(path.clone())
+        }
+        (_, _, Some(command)) => AccessTokenSource::Command(command.clone()),
+        (Some(token), _, _) => AccessTokenSource::Token(token.clone()),
+        (_, Some(path), _) => AccessTokenSource::File(path.clone()),
+        (None, None, None) => AccessTokenSource::None,
+    };
+    Ok(source)
+}
+
 fn get_config_profile(
     server_url: &Option<String>,
     profile: &Option<String>,
@@ -193,3 +320,110 @@ fn get_state_opt_out(profile: &Option<Profile>) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    fn source(args: &[&str], env: &[(&str, &str)]) -> Result<AccessTokenSource, clap::Error> {
+        let mut command = Cli::command();
+        // Read from variables unique to each call, so tests can't affect each other and aren't
+        // affected by the environment they run in
+        for id in ["access_token", "access_token_file"] {
+            let name = format!("BWS_TEST_{}_{}", id.to_uppercase(), args.join("_"));
+            match env.iter().find(|(env_id, _)| *env_id == id) {
+                Some((_, value)) => std::env::set_var(&name, value),
+                None => std::env::remove_var(&name),
+            }
+            command = command.mut_arg(id, |arg| arg.env(name));
+        }
+
+        let matches = command.try_get_matches_from(["bws"].iter().chain(args))?;
+        let cli = Cli::from_arg_matches(&matches)?;
+        access_token_source(&cli, &matches)
+    }
+
+    #[test]
+    fn access_token_source_command_line_beats_env() {
+        assert_eq!(
+            source(
+                &["--access-token-file", "token.txt"],
+                &[("access_token", "env")]
+            )
+            .unwrap(),
+            AccessTokenSource::File(PathBuf::from("token.txt"))
+        );
+        assert_eq!(
+            source(
+                &["--access-token-command", "pass bws"],
+                &[("access_token", "env")]
+            )
+            .unwrap(),
+            AccessTokenSource::Command("pass bws".to_string())
+        );
+        assert_eq!(
+            source(
+                &["--access-token", "token"],
+                &[("access_token_file", "env.txt")]
+            )
+            .unwrap(),
+            AccessTokenSource::Token("token".to_string())
+        );
+    }
+
+    #[test]
+    fn access_token_source_env_precedence() {
+        assert_eq!(
+            source(
+                &[],
+                &[("access_token", "env"), ("access_token_file", "env.txt")]
+            )
+            .unwrap(),
+            AccessTokenSource::Token("env".to_string())
+        );
+        assert_eq!(
+            source(&["--canonical"], &[("access_token_file", "env.txt")]).unwrap(),
+            AccessTokenSource::File(PathBuf::from("env.txt"))
+        );
+        assert_eq!(
+            source(&["--color", "no"], &[]).unwrap(),
+            AccessTokenSource::None
+        );
+    }
+
+    #[test]
+    fn access_token_source_rejects_multiple_flags() {
+        let error = source(
+            &[
+                "--access-token",
+                "token",
+                "--access-token-file",
+                "token.txt",
+            ],
+            &[],
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn run_manifest_allows_uuids_as_keynames_env_var() {
+        std::env::set_var(UUIDS_AS_KEYNAMES_VAR_NAME, "false");
+
+        let cli = Cli::try_parse_from(["bws", "run", "--env", "production", "--", "echo"]);
+
+        let Some(Commands::Run {
+            uuids_as_keynames,
+            environment,
+            ..
+        }) = cli.unwrap().command
+        else {
+            panic!("expected the run command");
+        };
+        assert!(!uuids_as_keynames);
+        assert_eq!(environment.as_deref(), Some("production"));
+    }
+}
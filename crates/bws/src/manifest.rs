@@ -0,0 +1,246 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use color_eyre::eyre::{bail, Result, WrapErr};
+use serde::Deserialize;
+use uuid::Uuid;
+
+pub(crate) const DEFAULT_MANIFEST_FILENAME: &str = "bws.yml";
+
+/// Maps environment variable names to secrets, optionally with per-environment overrides.
+///
+/// ```yaml
+/// project_id: 00000000-0000-0000-0000-000000000000
+/// variables:
+///   DATABASE_URL: DB_URL
+///   API_KEY:
+///     key: STRIPE_KEY
+///     project_id: 00000000-0000-0000-0000-000000000001
+/// environments:
+///   production:
+///     project_id: 00000000-0000-0000-0000-000000000002
+///     variables:
+///       DATABASE_URL: PROD_DB_URL
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Manifest {
+    pub(crate) project_id: Option<Uuid>,
+    #[serde(default)]
+    pub(crate) variables: BTreeMap<String, VariableSource>,
+    #[serde(default)]
+    pub(crate) environments: HashMap<String, ManifestEnvironment>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ManifestEnvironment {
+    pub(crate) project_id: Option<Uuid>,
+    #[serde(default)]
+    pub(crate) variables: BTreeMap<String, VariableSource>,
+}
+
+/// The secret an environment variable is read from, either just the secret key or ID, or a key
+/// together with the project to look it up in.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub(crate) enum VariableSource {
+    Key(String),
+    Secret {
+        key: String,
+        project_id: Option<Uuid>,
+    },
+}
+
+/// An environment variable with the secret it should be read from
+#[derive(Debug, PartialEq)]
+pub(crate) struct ManifestVariable {
+    pub(crate) name: String,
+    /// The secret key or secret ID
+    pub(crate) key: String,
+    /// The project to look up the secret in, or the whole organization if empty
+    pub(crate) project_id: Option<Uuid>,
+}
+
+impl Manifest {
+    pub(crate) fn load(path: &Path) -> Result<Manifest> {
+        let content = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read manifest {}", path.display()))?;
+        let manifest = serde_yaml::from_str(&content)
+            .wrap_err_with(|| format!("Failed to parse manifest {}", path.display()))?;
+        Ok(manifest)
+    }
+
+    /// Returns the variables to set for the environment, merging the environment section over
+    /// the top level variables. The project of a variable is the first one set in the variable,
+    /// the environment, `default_project_id` or the manifest, so a project given on the command
+    /// line overrides the one in the manifest but not the more specific ones.
+    pub(crate) fn variables(
+        &self,
+        environment: Option<&str>,
+        default_project_id: Option<Uuid>,
+    ) -> Result<Vec<ManifestVariable>> {
+        let default_project_id = default_project_id.or(self.project_id);
+
+        let mut variables: BTreeMap<&String, (&VariableSource, Option<Uuid>)> = self
+            .variables
+            .iter()
+            .map(|(name, source)| (name, (source, default_project_id)))
+            .collect();
+
+        if let Some(environment) = environment {
+            let Some(env) = self.environments.get(environment) else {
+                bail!(
+                    "Environment '{}' does not exist in the manifest",
+                    environment
+                );
+            };
+
+            let env_project_id = env.project_id.or(default_project_id);
+            for (name, source) in &env.variables {
+                variables.insert(name, (source, env_project_id));
+            }
+
+            // Variables inherited from the top level use the environment's project as well
+            if env.project_id.is_some() {
+                for (_, project_id) in variables.values_mut() {
+                    *project_id = env_project_id;
+                }
+            }
+        }
+
+        Ok(variables
+            .into_iter()
+            .map(|(name, (source, project_id))| match source {
+                VariableSource::Key(key) => ManifestVariable {
+                    name: name.clone(),
+                    key: key.clone(),
+                    project_id,
+                },
+                VariableSource::Secret {
+                    key,
+                    project_id: variable_project_id,
+                } => ManifestVariable {
+                    name: name.clone(),
+                    key: key.clone(),
+                    project_id: variable_project_id.or(project_id),
+                },
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = "
+project_id: 00000000-0000-0000-0000-000000000000
+variables:
+  DATABASE_URL: DB_URL
+  API_KEY:
+    key: STRIPE_KEY
+    project_id: 00000000-0000-0000-0000-000000000001
+environments:
+  production:
+    project_id: 00000000-0000-0000-0000-000000000002
+    variables:
+      DATABASE_URL: PROD_DB_URL
+  staging:
+    variables:
+      LOG_LEVEL: STAGING_LOG_LEVEL
+";
+
+    fn project(n: u128) -> Option<Uuid> {
+        Some(Uuid::from_u128(n))
+    }
+
+    fn variable(name: &str, key: &str, project_id: Option<Uuid>) -> ManifestVariable {
+        ManifestVariable {
+            name: name.to_string(),
+            key: key.to_string(),
+            project_id,
+        }
+    }
+
+    #[test]
+    fn manifest_top_level_variables() {
+        let manifest: Manifest = serde_yaml::from_str(MANIFEST).unwrap();
+
+        assert_eq!(
+            manifest.variables(None, None).unwrap(),
+            vec![
+                variable("API_KEY", "STRIPE_KEY", project(1)),
+                variable("DATABASE_URL", "DB_URL", project(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest_environment_overrides_variables_and_project() {
+        let manifest: Manifest = serde_yaml::from_str(MANIFEST).unwrap();
+
+        assert_eq!(
+            manifest.variables(Some("production"), None).unwrap(),
+            vec![
+                variable("API_KEY", "STRIPE_KEY", project(1)),
+                variable("DATABASE_URL", "PROD_DB_URL", project(2)),
+            ]
+        );
+        assert_eq!(
+            manifest.variables(Some("staging"), None).unwrap(),
+            vec![
+                variable("API_KEY", "STRIPE_KEY", project(1)),
+                variable("DATABASE_URL", "DB_URL", project(0)),
+                variable("LOG_LEVEL", "STAGING_LOG_LEVEL", project(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn manifest_unknown_environment_errors() {
+        let manifest: Manifest = serde_yaml::from_str(MANIFEST).unwrap();
+
+        assert!(manifest.variables(Some("development"), None).is_err());
+    }
+
+    #[test]
+    fn manifest_default_project_id() {
+        let manifest: Manifest = serde_yaml::from_str("variables:\n  A: B\n").unwrap();
+
+        assert_eq!(
+            manifest.variables(None, project(3)).unwrap(),
+            vec![variable("A", "B", project(3))]
+        );
+    }
+
+    #[test]
+    fn manifest_default_project_id_overrides_manifest_project() {
+        let manifest: Manifest = serde_yaml::from_str(MANIFEST).unwrap();
+
+        assert_eq!(
+            manifest.variables(None, project(3)).unwrap(),
+            vec![
+                variable("API_KEY", "STRIPE_KEY", project(1)),
+                variable("DATABASE_URL", "DB_URL", project(3)),
+            ]
+        );
+        assert_eq!(
+            manifest.variables(Some("production"), project(3)).unwrap(),
+            vec![
+                variable("API_KEY", "STRIPE_KEY", project(1)),
+                variable("DATABASE_URL", "PROD_DB_URL", project(2)),
+            ]
+        );
+        assert_eq!(
+            manifest.variables(Some("staging"), project(3)).unwrap(),
+            vec![
+                variable("API_KEY", "STRIPE_KEY", project(1)),
+                variable("DATABASE_URL", "DB_URL", project(3)),
+                variable("LOG_LEVEL", "STAGING_LOG_LEVEL", project(3)),
+            ]
+        );
+    }
+}
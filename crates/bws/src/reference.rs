@@ -0,0 +1,250 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
+
+use bitwarden::secrets_manager::secrets::SecretResponse;
+use color_eyre::eyre::{bail, Result};
+use regex::Regex;
+use uuid::Uuid;
+
+/// The maximum length of a value after expanding its references. Values referencing the same
+/// secret several times could otherwise grow exponentially with every level of nesting.
+const MAX_RESOLVED_LENGTH: usize = 1024 * 1024;
+
+/// Matches `{{bws:NAME}}`, where `NAME` is either a secret key or a secret ID
+const REFERENCE_REGEX: &str = r"\{\{[ \t]*bws:([^{}[:space:]]+)[ \t]*\}\}";
+
+fn reference_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(REFERENCE_REGEX).expect("REFERENCE_REGEX to be a valid regex"))
+}
+
+/// Returns the names of all the secrets referenced in the text.
+pub(crate) fn find_references(text: &str) -> Vec<&str> {
+    reference_regex()
+        .captures_iter(text)
+        .filter_map(|c| c.get(1))
+        .map(|m| m.as_str())
+        .collect()
+}
+
+/// A set of known secrets that references can be resolved against.
+#[derive(Debug, Default)]
+pub(crate) struct SecretReferences {
+    values: HashMap<Uuid, String>,
+    ids_by_key: HashMap<String, Vec<Uuid>>,
+    /// The names referenced in any of the known secret values
+    referenced: HashSet<String>,
+}
+
+impl SecretReferences {
+    pub(crate) fn new(secrets: &[SecretResponse]) -> Self {
+        let mut references = Self::default();
+        references.extend(secrets);
+        references
+    }
+
+    /// Adds secrets to the set, returning how many of them weren't known before.
+    pub(crate) fn extend(&mut self, secrets: &[SecretResponse]) -> usize {
+        let mut added = 0;
+        for secret in secrets {
+            if self
+                .values
+                .insert(secret.id, secret.value.clone())
+                .is_none()
+            {
+                self.ids_by_key
+                    .entry(secret.key.clone())
+                    .or_default()
+                    .push(secret.id);
+                self.referenced.extend(
+                    find_references(&secret.value)
+                        .into_iter()
+                        .map(ToOwned::to_owned),
+                );
+                added += 1;
+            }
+        }
+        added
+    }
+
+    /// Returns the referenced names in the known secrets which can't be found, so they can be
+    /// fetched in a single batch before resolving.
+    pub(crate) fn missing(&self) -> HashSet<String> {
+        self.referenced
+            .iter()
+            .filter(|name| self.lookup(name).is_none())
+            .cloned()
+            .collect()
+    }
+
+    /// Expands all references in the text. References inside referenced secrets are expanded as
+    /// well, failing if they form a cycle or the result gets too large.
+    pub(crate) fn resolve(&self, text: &str) -> Result<String> {
+        self.resolve_inner(
+            reference_regex(),
+            text,
+            &mut Vec::new(),
+            &mut HashMap::new(),
+        )
+    }
+
+    fn resolve_inner(
+        &self,
+        regex: &Regex,
+        text: &str,
+        stack: &mut Vec<Uuid>,
+        resolved: &mut HashMap<Uuid, String>,
+    ) -> Result<String> {
+        let mut result = String::with_capacity(text.len());
+        let mut last_end = 0;
+
+        for captures in regex.captures_iter(text) {
+            let (Some(reference), Some(name)) = (captures.get(0), captures.get(1)) else {
+                continue;
+            };
+
+            let id = match self.lookup(name.as_str()) {
+                Some(Ok(id)) => id,
+                Some(Err(count)) => bail!(
+                    "Secret reference '{}' is ambiguous, {} secrets have that name. Use the secret ID instead",
+                    name.as_str(),
+                    count
+                ),
+                None => bail!("Secret reference '{}' could not be found", name.as_str()),
+            };
+
+            // Each secret is only expanded once, later references reuse the result
+            if !resolved.contains_key(&id) {
+                if stack.contains(&id) {
+                    bail!("Secret reference '{}' is part of a cycle", name.as_str());
+                }
+
+                stack.push(id);
+                let value = self.resolve_inner(regex, &self.values[&id], stack, resolved)?;
+                stack.pop();
+                resolved.insert(id, value);
+            }
+
+            result.push_str(&text[last_end..reference.start()]);
+            result.push_str(&resolved[&id]);
+            last_end = reference.end();
+
+            if result.len() > MAX_RESOLVED_LENGTH {
+                bail!(
+                    "Secret references expand to more than {} bytes",
+                    MAX_RESOLVED_LENGTH
+                );
+            }
+        }
+
+        result.push_str(&text[last_end..]);
+        Ok(result)
+    }
+
+    /// Finds the secret ID for a name, or the number of matching secrets if it's ambiguous
+    fn lookup(&self, name: &str) -> Option<Result<Uuid, usize>> {
+        if let Ok(id) = Uuid::parse_str(name) {
+            if self.values.contains_key(&id) {
+                return Some(Ok(id));
+            }
+        }
+
+        match self.ids_by_key.get(name).map(Vec::as_slice) {
+            Some([id]) => Some(Ok(*id)),
+            Some(ids) if !ids.is_empty() => Some(Err(ids.len())),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::secret;
+
+    #[test]
+    fn find_references_matches_syntax() {
+        assert_eq!(
+            find_references("postgres://{{bws:DB_USER}}:{{ bws:DB_PASS }}@host"),
+            vec!["DB_USER", "DB_PASS"]
+        );
+        assert!(find_references("{{DB_USER}} {bws:DB_USER} {{bws:}}").is_empty());
+    }
+
+    #[test]
+    fn resolve_expands_by_key_and_id() {
+        let pass = secret("DB_PASS", "hunter2");
+        let text = format!("{{{{bws:DB_USER}}}}:{{{{bws:{}}}}}", pass.id);
+        let references = SecretReferences::new(&[secret("DB_USER", "admin"), pass]);
+
+        assert_eq!(references.resolve(&text).unwrap(), "admin:hunter2");
+    }
+
+    #[test]
+    fn resolve_expands_nested_references() {
+        let references = SecretReferences::new(&[
+            secret("HOST", "db.example.com"),
+            secret("URL", "postgres://{{bws:HOST}}/app"),
+        ]);
+
+        assert_eq!(
+            references.resolve("{{bws:URL}}").unwrap(),
+            "postgres://db.example.com/app"
+        );
+    }
+
+    #[test]
+    fn resolve_detects_cycles() {
+        let references =
+            SecretReferences::new(&[secret("A", "{{bws:B}}"), secret("B", "x{{bws:A}}")]);
+
+        assert!(references.resolve("{{bws:A}}").is_err());
+    }
+
+    #[test]
+    fn resolve_allows_repeated_references() {
+        let references = SecretReferences::new(&[secret("A", "a")]);
+
+        assert_eq!(references.resolve("{{bws:A}}{{bws:A}}").unwrap(), "aa");
+    }
+
+    #[test]
+    fn resolve_fails_on_ambiguous_or_unknown_names() {
+        let references = SecretReferences::new(&[secret("A", "1"), secret("A", "2")]);
+
+        assert!(references.resolve("{{bws:A}}").is_err());
+        assert!(references.resolve("{{bws:B}}").is_err());
+    }
+
+    #[test]
+    fn missing_lists_unknown_references() {
+        let references = SecretReferences::new(&[secret("A", "{{bws:A}} {{bws:B}}")]);
+
+        assert_eq!(references.missing(), HashSet::from(["B".to_string()]));
+    }
+
+    fn doubling_secrets(levels: usize) -> Vec<SecretResponse> {
+        let mut secrets = vec![secret("S0", "x")];
+        for i in 1..=levels {
+            let value = format!("{{{{bws:S{0}}}}}{{{{bws:S{0}}}}}", i - 1);
+            secrets.push(secret(&format!("S{i}"), &value));
+        }
+        secrets
+    }
+
+    #[test]
+    fn resolve_expands_repeated_nested_references() {
+        let references = SecretReferences::new(&doubling_secrets(10));
+
+        assert_eq!(references.resolve("{{bws:S10}}").unwrap(), "x".repeat(1024));
+    }
+
+    #[test]
+    fn resolve_limits_exponential_expansion() {
+        let references = SecretReferences::new(&doubling_secrets(64));
+
+        assert!(references.resolve("{{bws:S64}}").is_err());
+    }
+}
@@ -1,6 +1,13 @@
-use bitwarden::secrets_manager::{projects::ProjectResponse, secrets::SecretResponse};
+use std::collections::BTreeMap;
+
+use bitwarden::secrets_manager::{
+    projects::ProjectResponse,
+    secrets::SecretResponse,
+    terraform::{secrets_map, DuplicateSecretKeyError},
+};
 use bitwarden_cli::Color;
 use chrono::{DateTime, Utc};
+use color_eyre::{eyre::Result, Report};
 use comfy_table::Table;
 use serde::Serialize;
 
@@ -11,11 +18,16 @@ const ASCII_HEADER_ONLY: &str = "     --            ";
 pub(crate) struct OutputSettings {
     pub(crate) output: Output,
     pub(crate) color: Color,
+    pub(crate) canonical: bool,
 }
 
 impl OutputSettings {
-    pub(crate) fn new(output: Output, color: Color) -> Self {
-        OutputSettings { output, color }
+    pub(crate) fn new(output: Output, color: Color, canonical: bool) -> Self {
+        OutputSettings {
+            output,
+            color,
+            canonical,
+        }
     }
 }
 
@@ -25,15 +37,11 @@ pub(crate) fn serialize_response<T: Serialize + TableSerialize<N>, const N: usiz
 ) {
     match output_settings.output {
         Output::JSON => {
-            let mut text =
-                serde_json::to_string_pretty(&data).expect("Serialize should be infallible");
-            // Yaml/table/tsv serializations add a newline at the end, so we do the same here for
-            // consistency
-            text.push('\n');
+            let text = json_text(&data, output_settings.canonical);
             pretty_print("json", &text, output_settings.color);
         }
         Output::YAML => {
-            let text = serde_yaml::to_string(&data).expect("Serialize should be infallible");
+            let text = yaml_text(&data, output_settings.canonical);
             pretty_print("yaml", &text, output_settings.color);
         }
         Output::Env => {
@@ -82,10 +90,156 @@ pub(crate) fn serialize_response<T: Serialize + TableSerialize<N>, const N: usiz
                 .collect();
             println!("{}", rows.join("\n"));
         }
+        Output::Terraform => {
+            unreachable!("The terraform output format is rejected for commands other than secrets")
+        }
         Output::None => {}
     }
 }
 
+/// Same as [serialize_response], but also supports the terraform output format.
+pub(crate) fn serialize_secrets<
+    T: Serialize + TableSerialize<N> + TerraformSerialize,
+    const N: usize,
+>(
+    data: T,
+    output_settings: OutputSettings,
+) -> Result<()> {
+    if output_settings.output != Output::Terraform {
+        serialize_response(data, output_settings);
+        return Ok(());
+    }
+
+    let mut text = serde_json::to_string_pretty(&data.get_terraform_map()?)
+        .expect("Serialize should be infallible");
+    text.push('\n');
+    pretty_print("json", &text, output_settings.color);
+
+    Ok(())
+}
+
+fn json_text<T: Serialize>(data: &T, canonical: bool) -> String {
+    let mut text = if canonical {
+        serde_json::to_string_pretty(&canonical_value(data))
+    } else {
+        serde_json::to_string_pretty(data)
+    }
+    .expect("Serialize should be infallible");
+    // Yaml/table/tsv serializations add a newline at the end, so we do the same here for
+    // consistency
+    text.push('\n');
+    text
+}
+
+fn yaml_text<T: Serialize>(data: &T, canonical: bool) -> String {
+    if canonical {
+        serde_yaml::to_string(&canonical_value(data))
+    } else {
+        serde_yaml::to_string(data)
+    }
+    .expect("Serialize should be infallible")
+}
+
+/// Converts the data to a JSON value with the keys of every object sorted, so the output doesn't
+/// depend on the field order of the types and two outputs can be diffed. Array order is kept.
+///
+/// Objects in a [serde_json::Value] are already sorted unless serde_json's `preserve_order`
+/// feature is enabled, which any crate in the dependency tree could do, so the keys are sorted
+/// explicitly.
+pub(crate) fn canonical_value<T: Serialize>(data: &T) -> serde_json::Value {
+    fn sort_keys(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let sorted: BTreeMap<String, serde_json::Value> =
+                    map.into_iter().map(|(k, v)| (k, sort_keys(v))).collect();
+                serde_json::Value::Object(sorted.into_iter().collect())
+            }
+            serde_json::Value::Array(values) => {
+                serde_json::Value::Array(values.into_iter().map(sort_keys).collect())
+            }
+            value => value,
+        }
+    }
+
+    sort_keys(serde_json::to_value(data).expect("Serialize should be infallible"))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ErrorOutput {
+    /// A stable identifier for the kind of error, which scripts can branch on
+    pub(crate) code: &'static str,
+    pub(crate) message: String,
+    /// The HTTP status code, when the error was returned by the server
+    pub(crate) status: Option<u16>,
+    /// Whether retrying the same command may succeed
+    pub(crate) retryable: bool,
+}
+
+impl ErrorOutput {
+    pub(crate) fn from_report(report: &Report) -> Self {
+        // Include the causes, the outermost context alone rarely says what went wrong
+        let message = format!("{report:#}");
+
+        // The SDK error may be wrapped in other errors, so look through all of the causes
+        let sdk_error = report.chain().find_map(|cause| {
+            if let Some(error) = cause.downcast_ref::<bitwarden::Error>() {
+                return Some(error);
+            }
+            match cause.downcast_ref::<bitwarden::error::Error>() {
+                Some(bitwarden::error::Error::Core(error)) => Some(error),
+                None => None,
+            }
+        });
+
+        let Some(error) = sdk_error else {
+            return ErrorOutput {
+                code: "cli_error",
+                message,
+                status: None,
+                retryable: false,
+            };
+        };
+
+        let (code, status, retryable) = match error {
+            bitwarden::Error::ResponseContent { status, .. } => {
+                let status = status.as_u16();
+                let (code, retryable) = match status {
+                    401 => ("unauthorized", false),
+                    403 => ("forbidden", false),
+                    404 => ("not_found", false),
+                    429 => ("rate_limited", true),
+                    500.. => ("server_error", true),
+                    _ => ("request_failed", false),
+                };
+                (code, Some(status), retryable)
+            }
+            bitwarden::Error::Reqwest(_) => ("network_error", None, true),
+            bitwarden::Error::AccessTokenInvalid(_) => ("invalid_access_token", None, false),
+            // The identity server rejected the access token, e.g. because it was revoked or expired
+            bitwarden::Error::IdentityFail(_) => ("unauthorized", None, false),
+            bitwarden::Error::InvalidResponse | bitwarden::Error::Serde(_) => {
+                ("invalid_response", None, false)
+            }
+            _ => ("sdk_error", None, false),
+        };
+
+        ErrorOutput {
+            code,
+            message,
+            status,
+            retryable,
+        }
+    }
+}
+
+/// Prints the error as a JSON object to stderr.
+pub(crate) fn serialize_error(report: &Report) {
+    let text = serde_json::to_string_pretty(&ErrorOutput::from_report(report))
+        .expect("Serialize should be infallible");
+    eprintln!("{}", text);
+}
+
 fn pretty_print(language: &str, data: &str, color: Color) {
     if color.is_enabled() {
         bat::PrettyPrinter::new()
@@ -124,6 +278,16 @@ fn format_date(date: &DateTime<Utc>) -> String {
     date.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+impl TableSerialize<1> for String {
+    fn get_headers() -> [&'static str; 1] {
+        ["Value"]
+    }
+
+    fn get_values(&self) -> Vec<[String; 1]> {
+        vec![[self.clone()]]
+    }
+}
+
 impl TableSerialize<3> for ProjectResponse {
     fn get_headers() -> [&'static str; 3] {
         ["ID", "Name", "Creation Date"]
@@ -152,3 +316,234 @@ impl TableSerialize<4> for SecretResponse {
         ]]
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum SecretDiffStatus {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A secret which differs between two projects, as printed by `project diff`
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SecretDiff {
+    pub(crate) status: SecretDiffStatus,
+    pub(crate) key: String,
+    pub(crate) source_value: Option<String>,
+    pub(crate) target_value: Option<String>,
+}
+
+/// A flat map of secret key to value, as expected by the Terraform `external` data source. Only
+/// secrets can be printed this way.
+pub(crate) trait TerraformSerialize {
+    fn get_terraform_map(&self) -> Result<BTreeMap<String, String>, DuplicateSecretKeyError>;
+}
+
+impl TerraformSerialize for SecretResponse {
+    fn get_terraform_map(&self) -> Result<BTreeMap<String, String>, DuplicateSecretKeyError> {
+        secrets_map(std::slice::from_ref(self))
+    }
+}
+
+impl TerraformSerialize for Vec<SecretResponse> {
+    fn get_terraform_map(&self) -> Result<BTreeMap<String, String>, DuplicateSecretKeyError> {
+        secrets_map(self)
+    }
+}
+
+impl TableSerialize<4> for SecretDiff {
+    fn get_headers() -> [&'static str; 4] {
+        ["Status", "Key", "Source Value", "Target Value"]
+    }
+
+    fn get_values(&self) -> Vec<[String; 4]> {
+        let status = match self.status {
+            SecretDiffStatus::Added => "added",
+            SecretDiffStatus::Removed => "removed",
+            SecretDiffStatus::Changed => "changed",
+        };
+
+        vec![[
+            status.to_string(),
+            self.key.clone(),
+            self.source_value.clone().unwrap_or_default(),
+            self.target_value.clone().unwrap_or_default(),
+        ]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::secret;
+
+    #[test]
+    fn canonical_output_sorts_keys() {
+        let secrets = vec![secret("b", "2")];
+
+        // Secrets are serialized in field order by default, starting with the ID
+        assert!(json_text(&secrets, false).starts_with("[\n  {\n    \"id\""));
+        assert!(yaml_text(&secrets, false).starts_with("- id:"));
+
+        assert!(json_text(&secrets, true).starts_with("[\n  {\n    \"creationDate\""));
+        assert!(yaml_text(&secrets, true).starts_with("- creationDate:"));
+        assert_eq!(canonical_value(&secrets)[0]["key"], "b");
+    }
+
+    #[test]
+    fn terraform_map_is_keyed_by_name_and_sorted() {
+        let secrets = vec![secret("b", "2"), secret("a", "1")];
+
+        let map = secrets.get_terraform_map().unwrap();
+
+        assert_eq!(
+            map.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "2".to_string())
+            ]
+        );
+    }
+
+    fn response_error(status: u16) -> Report {
+        bitwarden::Error::ResponseContent {
+            status: reqwest::StatusCode::from_u16(status).unwrap(),
+            message: "error".to_string(),
+        }
+        .into()
+    }
+
+    fn reqwest_error() -> reqwest::Error {
+        reqwest::Client::new().get("not a url").build().unwrap_err()
+    }
+
+    #[test]
+    fn error_output_response_content() {
+        for (status, code, retryable) in [
+            (401, "unauthorized", false),
+            (403, "forbidden", false),
+            (404, "not_found", false),
+            (429, "rate_limited", true),
+            (500, "server_error", true),
+            (503, "server_error", true),
+            (400, "request_failed", false),
+        ] {
+            let output = ErrorOutput::from_report(&response_error(status));
+
+            assert_eq!(output.code, code);
+            assert_eq!(output.status, Some(status));
+            assert_eq!(output.retryable, retryable);
+        }
+    }
+
+    #[test]
+    fn error_output_reqwest() {
+        let report = Report::new(bitwarden::Error::Reqwest(reqwest_error()));
+
+        let output = ErrorOutput::from_report(&report);
+
+        assert_eq!(output.code, "network_error");
+        assert_eq!(output.status, None);
+        assert!(output.retryable);
+    }
+
+    #[test]
+    fn error_output_access_token_invalid() {
+        let error: bitwarden::Error = "not a token"
+            .parse::<bitwarden::auth::AccessToken>()
+            .unwrap_err()
+            .into();
+
+        let output = ErrorOutput::from_report(&Report::new(error));
+
+        assert_eq!(output.code, "invalid_access_token");
+        assert!(!output.retryable);
+    }
+
+    #[test]
+    fn error_output_identity_fail() {
+        // The response type isn't exported by the SDK, so it's parsed like the SDK does
+        let error = serde_json::from_str(
+            r#"{
+                "error": "invalid_client",
+                "error_description": "invalid_client",
+                "ErrorModel": { "Message": "Access token is expired", "Object": "error" }
+            }"#,
+        )
+        .map(bitwarden::Error::IdentityFail)
+        .unwrap();
+
+        let output = ErrorOutput::from_report(&Report::new(error));
+
+        assert_eq!(output.code, "unauthorized");
+        assert_eq!(output.status, None);
+        assert!(!output.retryable);
+    }
+
+    #[test]
+    fn error_output_invalid_response() {
+        let serde_error = serde_json::from_str::<serde_json::Value>("<html>").unwrap_err();
+
+        for error in [
+            bitwarden::Error::InvalidResponse,
+            bitwarden::Error::Serde(serde_error),
+        ] {
+            let output = ErrorOutput::from_report(&Report::new(error));
+
+            assert_eq!(output.code, "invalid_response");
+            assert!(!output.retryable);
+        }
+    }
+
+    #[test]
+    fn error_output_wrapped_errors() {
+        let wrapped = bitwarden::error::Error::Core(bitwarden::Error::NotAuthenticated);
+        assert_eq!(
+            ErrorOutput::from_report(&Report::new(wrapped)).code,
+            "sdk_error"
+        );
+
+        let report = response_error(404).wrap_err("Failed to get the secret");
+        let output = ErrorOutput::from_report(&report);
+        assert_eq!(output.code, "not_found");
+        assert_eq!(
+            output.message,
+            "Failed to get the secret: Received error message from server: [404 Not Found] error"
+        );
+    }
+
+    #[test]
+    fn error_output_io_error_causes() {
+        let report = Report::new(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .wrap_err("Failed to read manifest bws.yml");
+
+        let output = ErrorOutput::from_report(&report);
+
+        assert_eq!(output.code, "cli_error");
+        assert_eq!(
+            output.message,
+            "Failed to read manifest bws.yml: entity not found"
+        );
+    }
+
+    #[test]
+    fn error_output_cli_error() {
+        let report = color_eyre::eyre::eyre!("Missing access token");
+
+        let output = ErrorOutput::from_report(&report);
+
+        assert_eq!(output.code, "cli_error");
+        assert_eq!(output.message, "Missing access token");
+        assert_eq!(output.status, None);
+        assert!(!output.retryable);
+    }
+
+    #[test]
+    fn terraform_map_rejects_duplicate_keys() {
+        let secrets = vec![secret("a", "1"), secret("a", "2")];
+
+        assert!(secrets.get_terraform_map().is_err());
+    }
+}
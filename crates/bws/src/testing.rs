@@ -0,0 +1,19 @@
+//! Fixtures shared by the tests of different modules
+
+use bitwarden::secrets_manager::secrets::SecretResponse;
+use chrono::Utc;
+use uuid::Uuid;
+
+/// A secret without a project. Use struct update syntax to change the other fields.
+pub(crate) fn secret(key: &str, value: &str) -> SecretResponse {
+    SecretResponse {
+        id: Uuid::new_v4(),
+        organization_id: Uuid::nil(),
+        project_id: None,
+        key: key.to_string(),
+        value: value.to_string(),
+        note: String::new(),
+        creation_date: Utc::now(),
+        revision_date: Utc::now(),
+    }
+}
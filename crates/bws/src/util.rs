@@ -1,5 +1,13 @@
+use std::{
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use color_eyre::eyre::{bail, Result, WrapErr};
 use regex::Regex;
 use uuid::Uuid;
+use zeroize::Zeroizing;
 
 const VALID_POSIX_NAME_REGEX: &str = "^[a-zA-Z_][a-zA-Z0-9_]*$";
 const STRING_TO_BOOL_ERROR_MESSAGE: &str = "Could not convert string to bool";
@@ -26,6 +34,87 @@ pub(crate) fn uuid_to_posix(uuid: &Uuid) -> String {
     format!("_{}", uuid.to_string().replace('-', "_"))
 }
 
+/// Where the contents of a value argument should be read from.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ValueSource<'a> {
+    /// The argument itself is the value
+    Literal(&'a str),
+    /// `-` reads the value from stdin
+    Stdin,
+    /// `@path` reads the value from a file
+    File(&'a Path),
+}
+
+impl<'a> ValueSource<'a> {
+    /// Parses a value argument. A leading `@@` escapes a literal value starting with `@`.
+    pub(crate) fn parse(value: &'a str) -> Self {
+        if value == "-" {
+            ValueSource::Stdin
+        } else if value.starts_with("@@") {
+            ValueSource::Literal(&value[1..])
+        } else if let Some(path) = value.strip_prefix('@') {
+            ValueSource::File(Path::new(path))
+        } else {
+            ValueSource::Literal(value)
+        }
+    }
+
+    pub(crate) fn read(&self) -> Result<String> {
+        match self {
+            ValueSource::Literal(value) => Ok(value.to_string()),
+            ValueSource::Stdin => {
+                let mut buffer = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut buffer)
+                    .wrap_err("Failed to read value from stdin")?;
+                Ok(buffer)
+            }
+            ValueSource::File(path) => std::fs::read_to_string(path)
+                .wrap_err_with(|| format!("Failed to read value from {}", path.display())),
+        }
+    }
+}
+
+/// Reads the access token from a file, ignoring surrounding whitespace like a trailing newline.
+pub(crate) fn read_access_token_file(path: &Path) -> Result<String> {
+    let content = Zeroizing::new(
+        std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read access token file {}", path.display()))?,
+    );
+    trim_access_token(&content, "Access token file")
+}
+
+/// Runs a command through the shell and reads the access token from its output. Stdin and stderr
+/// are inherited so the command can prompt the user.
+pub(crate) fn run_access_token_command(command: &str) -> Result<String> {
+    let shell = if cfg!(windows) { "powershell" } else { "sh" };
+
+    let output = Command::new(shell)
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .wrap_err("Failed to execute access token command")?;
+    let stdout = Zeroizing::new(output.stdout);
+
+    if !output.status.success() {
+        bail!("Access token command failed with {}", output.status);
+    }
+
+    let content =
+        std::str::from_utf8(&stdout).wrap_err("Access token command output is not valid UTF-8")?;
+    trim_access_token(content, "Access token command output")
+}
+
+fn trim_access_token(content: &str, source: &str) -> Result<String> {
+    let access_token = content.trim();
+    if access_token.is_empty() {
+        bail!("{} is empty", source);
+    }
+    Ok(access_token.to_string())
+}
+
 mod tests {
     #[allow(unused_imports)]
     use super::*;
@@ -90,4 +179,63 @@ mod tests {
         let result = string_to_bool("hello world");
         assert_eq!(result, Err(STRING_TO_BOOL_ERROR_MESSAGE));
     }
+
+    #[test]
+    fn test_value_source_parse() {
+        assert_eq!(ValueSource::parse("value"), ValueSource::Literal("value"));
+        assert_eq!(ValueSource::parse(""), ValueSource::Literal(""));
+        assert_eq!(ValueSource::parse("-"), ValueSource::Stdin);
+        assert_eq!(ValueSource::parse("--"), ValueSource::Literal("--"));
+        assert_eq!(
+            ValueSource::parse("@cert.pem"),
+            ValueSource::File(Path::new("cert.pem"))
+        );
+        assert_eq!(ValueSource::parse("@@user"), ValueSource::Literal("@user"));
+    }
+
+    #[test]
+    fn test_value_source_read_file() {
+        use std::io::Write;
+
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        write!(tmpfile.as_file(), "line 1\nline 2\n").unwrap();
+
+        let value = ValueSource::File(tmpfile.path()).read().unwrap();
+        assert_eq!(value, "line 1\nline 2\n");
+    }
+
+    #[test]
+    fn test_value_source_read_missing_file_errors() {
+        assert!(ValueSource::File(Path::new("non_existing")).read().is_err());
+    }
+
+    #[test]
+    fn test_read_access_token_file_trims_whitespace() {
+        use std::io::Write;
+
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        writeln!(tmpfile.as_file(), "  0.token").unwrap();
+
+        assert_eq!(read_access_token_file(tmpfile.path()).unwrap(), "0.token");
+    }
+
+    #[test]
+    fn test_read_access_token_file_empty_errors() {
+        use std::io::Write;
+
+        let tmpfile = tempfile::NamedTempFile::new().unwrap();
+        writeln!(tmpfile.as_file()).unwrap();
+
+        assert!(read_access_token_file(tmpfile.path()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run_access_token_command() {
+        assert_eq!(
+            run_access_token_command("printf '0.token\\n'").unwrap(),
+            "0.token"
+        );
+        assert!(run_access_token_command("exit 1").is_err());
+    }
 }
@@ -97,6 +97,7 @@ struct SchemaTypes {
     input_command: bitwarden_json::command::Command,
 
     // Output types for Client::run_command
+    capabilities: Response<bitwarden_json::response::CapabilitiesResponse>,
     api_key_login: Response<bitwarden::auth::login::ApiKeyLoginResponse>,
     password_login: Response<bitwarden::auth::login::PasswordLoginResponse>,
     login_access_token: Response<bitwarden::auth::login::AccessTokenLoginResponse>,
@@ -10,6 +10,7 @@ module BitwardenSDKSecrets
       attribute :secrets,            SecretsCommand.optional.default(nil)
       attribute :projects,           ProjectsCommand.optional.default(nil)
       attribute :generators,         GeneratorsCommand.optional.default(nil)
+      attribute :get_capabilities,   Types::Hash.optional.default(nil)
 
       def to_dynamic
         {
@@ -22,6 +23,7 @@ module BitwardenSDKSecrets
           "secrets"          => secrets&.to_dynamic,
           "projects"         => projects&.to_dynamic,
           "generators"       => generators&.to_dynamic,
+          "getCapabilities"  => get_capabilities,
         }.compact
       end
     end